@@ -33,6 +33,7 @@ use cairo_vm::air_public_input::PublicInputError;
 use cairo_vm::cairo_run;
 use cairo_vm::cairo_run::EncodeTraceError;
 use cairo_vm::hint_processor::cairo_1_hint_processor::hint_processor::Cairo1HintProcessor;
+use cairo_vm::hint_processor::hint_processor_definition::HintProcessor;
 use cairo_vm::serde::deserialize_program::BuiltinName;
 use cairo_vm::serde::deserialize_program::{ApTracking, FlowTrackingData, HintParams};
 use cairo_vm::stdlib::collections::HashMap;
@@ -43,21 +44,28 @@ use cairo_vm::vm::errors::runner_errors::RunnerError;
 use cairo_vm::vm::errors::trace_errors::TraceError;
 use cairo_vm::vm::errors::vm_errors::VirtualMachineError;
 use cairo_vm::vm::runners::builtin_runner::{
-    BITWISE_BUILTIN_NAME, EC_OP_BUILTIN_NAME, HASH_BUILTIN_NAME, OUTPUT_BUILTIN_NAME,
-    POSEIDON_BUILTIN_NAME, RANGE_CHECK_BUILTIN_NAME, SIGNATURE_BUILTIN_NAME,
+    BuiltinRunner, BITWISE_BUILTIN_NAME, EC_OP_BUILTIN_NAME, HASH_BUILTIN_NAME,
+    OUTPUT_BUILTIN_NAME, POSEIDON_BUILTIN_NAME, RANGE_CHECK_BUILTIN_NAME, SIGNATURE_BUILTIN_NAME,
 };
 use cairo_vm::vm::runners::cairo_runner::RunnerMode;
 use cairo_vm::{
     serde::deserialize_program::ReferenceManager,
-    types::{program::Program, relocatable::MaybeRelocatable},
+    types::{
+        program::Program,
+        relocatable::{MaybeRelocatable, Relocatable},
+    },
     vm::{
-        runners::cairo_runner::{CairoRunner, RunResources},
+        runners::cairo_runner::{CairoRunner, ExecutionResources, RunResources},
         vm_core::VirtualMachine,
     },
     Felt252,
 };
 use itertools::chain;
 use serde::Deserialize;
+
+pub mod interpreter;
+pub mod syscall_handler;
+
 use serde::Serialize;
 use std::fmt;
 use std::fmt::Display;
@@ -70,6 +78,25 @@ use thiserror_no_std::Error;
 pub enum FuncArg {
     Array(Vec<Felt252>),
     Single(Felt252),
+    /// A wide integer (e.g. `u256`, `u512`) already split into its little-endian
+    /// 128-bit limbs. `u256` is `[low, high]` (two limbs); `u512` is four limbs. The
+    /// limbs are laid out inline, one felt per limb, matching the parameter's
+    /// `ty_size`.
+    Wide(Vec<Felt252>),
+}
+
+impl FuncArg {
+    /// Builds a [`FuncArg::Wide`] from a `u256` given its little-endian 128-bit limbs
+    /// `[low, high]`.
+    pub fn u256(low: u128, high: u128) -> Self {
+        FuncArg::Wide(vec![Felt252::from(low), Felt252::from(high)])
+    }
+
+    /// Builds a [`FuncArg::Wide`] from a `u512` given its four little-endian 128-bit
+    /// limbs.
+    pub fn u512(limbs: [u128; 4]) -> Self {
+        FuncArg::Wide(limbs.iter().copied().map(Felt252::from).collect())
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -134,8 +161,21 @@ pub enum Error {
     Program(#[from] ProgramError),
     #[error(transparent)]
     Memory(#[from] MemoryError),
-    #[error("Program panicked with {0:?}")]
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+    #[error("Program panicked with: {}", decode_felts_as_short_string(.0))]
     RunPanic(Vec<Felt252>),
+    #[error("{inner}\nCairo traceback (most recent call first):\n{}", format_traceback(.traceback))]
+    VmExceptionWithTrace {
+        inner: Box<Error>,
+        traceback: Vec<(usize, String)>,
+    },
+    #[error("Function return type must be a single Array<Felt252>")]
+    IllegalReturnValue,
+    #[error("Function params must only be of type Array<Felt252>")]
+    IllegalInputValue,
+    #[error("Could not find the output builtin segment")]
+    MissingOutputBuiltin,
     #[error("Function signature has no return types")]
     NoRetTypesInSignature,
     #[error("No size for concrete type id: {0}")]
@@ -153,6 +193,23 @@ pub enum Error {
         param_index: usize,
         arg_index: usize,
     },
+    #[error("Interpreter encountered a value of an unexpected type")]
+    InterpreterTypeMismatch,
+    #[error("Interpreter ran out of arguments while seeding function parameters")]
+    InterpreterMissingArgument,
+    #[error("Interpreter reached an invalid program counter")]
+    InterpreterInvalidPc,
+    #[error("Interpreter encountered an unsupported libfunc")]
+    InterpreterUnsupportedLibfunc,
+    #[error("Interpreter referenced an unbound variable")]
+    InterpreterUnboundVariable,
+    #[error("Interpreter attempted a felt252 division by zero")]
+    InterpreterDivisionByZero,
+    #[error("Differential validation mismatch: CASM returned {casm:?}, interpreter returned {interpreter:?}")]
+    DifferentialMismatch {
+        casm: Vec<Felt252>,
+        interpreter: Vec<Felt252>,
+    },
 }
 
 pub struct FileWriter {
@@ -188,17 +245,64 @@ impl FileWriter {
     }
 }
 
+/// Knobs that control how a Sierra program is compiled and run.
+///
+/// Benchmarking needs to compare execution-mode step counts against proof-mode
+/// overhead and to try restricted layouts (e.g. `recursive`, `starknet`) that only
+/// enable the builtins a given model uses, so these values — previously hardcoded in
+/// [`run`] — are surfaced here. [`Cairo1RunConfig::default`] selects proof mode on the
+/// `all_cairo` layout.
+///
+/// Note that proof mode implies output serialization (`append_return_values`), which in
+/// turn constrains `main` to take and return only `Array<Felt252>`
+/// ([`Error::IllegalInputValue`]/[`Error::IllegalReturnValue`]). To run a `main` with an
+/// arbitrary signature, set `proof_mode: false`.
+pub struct Cairo1RunConfig<'a> {
+    /// Run in (embedded) proof mode, emitting the canonical header and finalizing
+    /// segments so an AIR public input can be produced.
+    pub proof_mode: bool,
+    /// The VM layout, which determines the set of available builtins.
+    pub layout: &'a str,
+    /// Initial gas made available to the program.
+    pub initial_gas: usize,
+    /// Whether to record the execution trace. The trace is recorded regardless when a
+    /// trace file or an AIR public input is requested.
+    pub trace_enabled: bool,
+    /// Whether to compute gas usage metadata and check it while running.
+    pub gas_usage_check: bool,
+    /// Path to write the AIR public input to, when proving.
+    pub air_public_input: Option<PathBuf>,
+}
+
+impl Default for Cairo1RunConfig<'_> {
+    fn default() -> Self {
+        Self {
+            proof_mode: true,
+            layout: "all_cairo",
+            initial_gas: 9999999999999,
+            trace_enabled: true,
+            gas_usage_check: true,
+            air_public_input: None,
+        }
+    }
+}
+
 pub async fn run(
     sierra_program: &SierraProgram,
     trace_file: &Option<PathBuf>,
     memory_file: &Option<PathBuf>,
+    cairo_pie_output: &Option<PathBuf>,
+    execution_summary_output: &Option<PathBuf>,
     args: &FuncArgs,
+    append_return_values: bool,
+    config: &Cairo1RunConfig<'_>,
+    syscall_handler: Option<&mut dyn syscall_handler::SyscallHandler>,
 ) -> Result<ReturnValueVec, Error> {
-    let layout = "all_cairo";
-    let proof_mode = true;
-    let air_public_input: Option<PathBuf> = None;
+    // Serializing the result into the output segment is always done in proof mode, so
+    // that the proof commits to the program's inputs and outputs.
+    let append_return_values = append_return_values || config.proof_mode;
 
-    let metadata_config = Some(Default::default());
+    let metadata_config = config.gas_usage_check.then(Default::default);
 
     let gas_usage_check = metadata_config.is_some();
     let metadata = create_metadata(&sierra_program, metadata_config)?;
@@ -210,18 +314,24 @@ pub async fn run(
 
     let main_func = find_function(&sierra_program, "::main")?;
 
-    let initial_gas = 9999999999999_usize;
+    // When serializing into the output segment we can only handle `Array<Felt252>`
+    // inputs and a single `Array<Felt252>` return value, since that is the only layout
+    // the canonical length-prefixed format is defined for.
+    if append_return_values {
+        check_only_array_felt_input_type(&sierra_program_registry, main_func)?;
+        check_only_array_felt_return_type(main_func)?;
+    }
 
     // Modified entry code to be compatible with custom cairo1 Proof Mode.
     // This adds code that's needed for dictionaries, adjusts ap for builtin pointers, adds initial gas for the gas builtin if needed, and sets up other necessary code for cairo1
-    let (entry_code, builtins) = create_entry_code(
+    let (entry_code, builtins, args_start_offset) = create_entry_code(
         &sierra_program_registry,
         &casm_program,
         &type_sizes,
         main_func,
-        initial_gas,
-        proof_mode,
+        append_return_values,
         &args.0,
+        config,
     )?;
 
     // Get the user program instructions
@@ -230,7 +340,7 @@ pub async fn run(
     // This footer is used by lib funcs
     let libfunc_footer = create_code_footer();
 
-    let proof_mode_header = if proof_mode {
+    let proof_mode_header = if config.proof_mode {
         println!("Compiling with proof mode and running ...");
 
         // This information can be useful for the users using the prover.
@@ -247,6 +357,15 @@ pub async fn run(
         casm! {}.instructions
     };
 
+    // Number of felt cells preceding the user program's instructions (the proof mode
+    // header plus the argument-setup entry code). Used to map a pc back to the
+    // originating Sierra statement when building a traceback.
+    let program_base_offset = proof_mode_header
+        .iter()
+        .chain(entry_code.iter())
+        .map(|inst| inst.body.op_size())
+        .sum::<usize>();
+
     // This is the program we are actually running/proving
     // With (embedded proof mode), cairo1 header and the libfunc footer
     let instructions = chain!(
@@ -258,7 +377,7 @@ pub async fn run(
 
     let (processor_hints, program_hints) = build_hints_vec(instructions.clone());
 
-    let mut hint_processor = Cairo1HintProcessor::new(&processor_hints, RunResources::default());
+    let base_hint_processor = Cairo1HintProcessor::new(&processor_hints, RunResources::default());
 
     let data: Vec<MaybeRelocatable> = instructions
         .flat_map(|inst| inst.assemble().encode())
@@ -268,7 +387,7 @@ pub async fn run(
 
     let data_len = data.len();
 
-    let program = if proof_mode {
+    let program = if config.proof_mode {
         Program::new_for_proof(
             builtins,
             data,
@@ -299,21 +418,56 @@ pub async fn run(
         )?
     };
 
-    let runner_mode = if proof_mode {
+    let runner_mode = if config.proof_mode {
         RunnerMode::ProofModeCairo1
     } else {
         RunnerMode::ExecutionMode
     };
 
-    let mut runner = CairoRunner::new_v2(&program, &layout, runner_mode)?;
-    let mut vm = VirtualMachine::new(trace_file.is_some() || air_public_input.is_some());
+    let mut runner = CairoRunner::new_v2(&program, &config.layout, runner_mode)?;
+    // Writing a trace file or an AIR public input both require a relocated trace, so
+    // capture the trace whenever either output is requested even if the caller left
+    // `trace_enabled` off — otherwise the run would proceed and only fail at write time
+    // with `TraceError::TraceNotRelocated`.
+    let trace_enabled =
+        config.trace_enabled || trace_file.is_some() || config.air_public_input.is_some();
+    let mut vm = VirtualMachine::new(trace_enabled);
     let end = runner.initialize(&mut vm)?;
 
     additional_initialization(&mut vm, data_len)?;
 
-    // Run it until the end/ infinite loop in proof_mode
-    runner.run_until_pc(end, &mut vm, &mut hint_processor)?;
-    runner.end_run(false, false, &mut vm, &mut hint_processor)?;
+    // Write the argument felts and their array segments directly into VM memory.
+    // The entry code only reserves the cells and references the populated segment
+    // pointers, so its instruction count stays constant regardless of input size.
+    // In proof mode the canonical header (`call rel 4; jmp rel 0;`) runs before the
+    // entry code and advances `ap` by 2, so the reserved argument cells are shifted
+    // by the same amount relative to the post-initialization `ap`.
+    let header_offset = if config.proof_mode { 2 } else { 0 };
+    load_arguments(&mut vm, &args.0, args_start_offset + header_offset)?;
+
+    // When a syscall handler is supplied (e.g. for a `#[starknet::contract]` entrypoint),
+    // wrap the base hint processor so its system calls are dispatched to the handler;
+    // otherwise run directly on the base processor.
+    let mut base_hint_processor = base_hint_processor;
+    let mut wrapped_hint_processor;
+    let hint_processor: &mut dyn HintProcessor = match syscall_handler {
+        Some(handler) => {
+            wrapped_hint_processor =
+                syscall_handler::SyscallHintProcessor::new(base_hint_processor, handler);
+            &mut wrapped_hint_processor
+        }
+        None => &mut base_hint_processor,
+    };
+
+    // Run it until the end/ infinite loop in proof_mode. On failure, decode a
+    // traceback so the originating Sierra statements are surfaced instead of a bare
+    // VM error with no location context.
+    if let Err(error) = runner.run_until_pc(end, &mut vm, hint_processor) {
+        return Err(build_vm_exception(&vm, &casm_program, program_base_offset, error.into()));
+    }
+    if let Err(error) = runner.end_run(false, false, &mut vm, hint_processor) {
+        return Err(build_vm_exception(&vm, &casm_program, program_base_offset, error.into()));
+    }
 
     // Fetch return type data
     let return_type_id = main_func
@@ -363,8 +517,50 @@ pub async fn run(
         }
     }
 
-    // Set stop pointers for builtins so we can obtain the air public input
-    if air_public_input.is_some() {
+    // The entry code serialized the (panic-unwrapped) return array and the flat inputs
+    // into the output builtin segment in-program, in the canonical length-prefixed
+    // layout `[out_len, out.., in_len, in..]`. Derive the final output pointer that copy
+    // leaves behind so the builtin's stop pointer can be finalized below; the cells
+    // themselves are already written (and trace-constrained) by the executed loop.
+    let output_stop_pointer = if append_return_values {
+        let output_base = vm
+            .get_builtin_runners()
+            .iter()
+            .find_map(|builtin| match builtin {
+                BuiltinRunner::Output(output) => Some(output.base()),
+                _ => None,
+            })
+            .ok_or(Error::MissingOutputBuiltin)?;
+        let array_start = return_values
+            .first()
+            .ok_or(Error::FailedToExtractReturnValues)?
+            .get_relocatable()
+            .ok_or(Error::FailedToExtractReturnValues)?;
+        let array_end = return_values
+            .get(1)
+            .ok_or(Error::FailedToExtractReturnValues)?
+            .get_relocatable()
+            .ok_or(Error::FailedToExtractReturnValues)?;
+        let output_len = (array_end - array_start).map_err(VirtualMachineError::Math)?;
+        let input_len: usize = args
+            .0
+            .iter()
+            .map(|arg| match arg {
+                FuncArg::Single(_) => 1,
+                FuncArg::Array(values) => values.len(),
+                FuncArg::Wide(limbs) => limbs.len(),
+            })
+            .sum();
+        // `[out_len] + out + [in_len] + in`
+        let total_cells = 1 + output_len + 1 + input_len;
+        Some(Relocatable::from((output_base as isize, total_cells)))
+    } else {
+        None
+    };
+
+    // Set stop pointers for builtins and finalize the execution public memory. This
+    // bookkeeping is only meaningful in proof mode; execution mode skips it.
+    if config.proof_mode {
         // Cairo 1 programs have other return values aside from the used builtin's final pointers, so we need to hand-pick them
         let ret_types_sizes = main_func
             .signature
@@ -402,6 +598,13 @@ pub async fn run(
             }
             stack_pointer.offset += size as usize;
         }
+        // `main` neither takes nor returns the output builtin, so it gets no entry from
+        // the return-value scan above. Supply its final stack pointer explicitly from the
+        // in-program serialization, otherwise `finalize_segments` would leave the output
+        // segment without a stop pointer.
+        if let Some(output_pointer) = output_stop_pointer {
+            builtin_name_to_stack_pointer.insert(OUTPUT_BUILTIN_NAME, output_pointer);
+        }
         // Set stop pointer for each builtin
         vm.builtins_final_stack_from_stack_pointer_dict(&builtin_name_to_stack_pointer)?;
 
@@ -411,7 +614,7 @@ pub async fn run(
 
     runner.relocate(&mut vm, true)?;
 
-    if let Some(file_path) = air_public_input {
+    if let Some(file_path) = &config.air_public_input {
         let json = runner.get_air_public_input(&vm)?.serialize_json()?;
         std::fs::write(file_path, json)?;
     }
@@ -436,11 +639,85 @@ pub async fn run(
         memory_writer.flush()?;
     }
 
+    // Emit a CairoPie so the run can be packaged as a task and fed through a
+    // bootloader for proof aggregation instead of being proven in isolation. The pie
+    // captures the relocated memory, execution resources, the builtins' additional
+    // data and the program hash.
+    if let Some(pie_path) = cairo_pie_output {
+        runner.get_cairo_pie(&vm)?.write_zip_file(pie_path)?;
+    }
+
+    // Collect the run's cost breakdown (step count, per-builtin instance counts,
+    // memory holes) as machine-readable JSON so callers can aggregate builtin usage
+    // across many ML ops without scraping stdout.
+    if let Some(summary_path) = execution_summary_output {
+        let segment_sizes = vm.get_segment_used_sizes().unwrap_or_default();
+        let summary =
+            ExecutionSummary::from_resources(&runner.get_execution_resources(&vm)?, segment_sizes);
+        std::fs::write(summary_path, serde_json::to_string_pretty(&summary)?)?;
+    }
+
     let return_values = fetch_arrays_from_memory(&vm, return_values.clone());
 
     return_values
 }
 
+/// Per-builtin instance counts for a run, keyed by builtin.
+///
+/// Mirrors the builtins the runner tracks so callers get a stable, named breakdown
+/// instead of a string-keyed map whose keys depend on the VM version.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct BuiltinInstanceCounter {
+    pub range_check: usize,
+    pub pedersen: usize,
+    pub poseidon: usize,
+    pub bitwise: usize,
+    pub ec_op: usize,
+    pub ecdsa: usize,
+    pub output: usize,
+}
+
+/// A machine-readable cost breakdown of a single run.
+///
+/// Collected from the runner's execution resources after `end_run`, this lets callers
+/// aggregate builtin usage across many ML ops the way the AIR public input's segment
+/// data is derived, without parsing the builtins printed to stdout.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct ExecutionSummary {
+    pub n_steps: usize,
+    pub n_memory_holes: usize,
+    /// Used size of each memory segment, indexed by segment, as the AIR public input
+    /// derives them after relocation.
+    pub segment_sizes: Vec<usize>,
+    pub builtin_instance_counter: BuiltinInstanceCounter,
+}
+
+impl ExecutionSummary {
+    fn from_resources(resources: &ExecutionResources, segment_sizes: Vec<usize>) -> Self {
+        let count = |name: &str| {
+            resources
+                .builtin_instance_counter
+                .get(name)
+                .copied()
+                .unwrap_or_default()
+        };
+        Self {
+            n_steps: resources.n_steps,
+            n_memory_holes: resources.n_memory_holes,
+            segment_sizes,
+            builtin_instance_counter: BuiltinInstanceCounter {
+                range_check: count(RANGE_CHECK_BUILTIN_NAME),
+                pedersen: count(HASH_BUILTIN_NAME),
+                poseidon: count(POSEIDON_BUILTIN_NAME),
+                bitwise: count(BITWISE_BUILTIN_NAME),
+                ec_op: count(EC_OP_BUILTIN_NAME),
+                ecdsa: count(SIGNATURE_BUILTIN_NAME),
+                output: count(OUTPUT_BUILTIN_NAME),
+            },
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub enum ReturnValue {
     Int(Felt252),
@@ -512,6 +789,167 @@ fn fetch_arrays_from_memory(
     Ok(ReturnValueVec(arrays))
 }
 
+/// Checks that `main`'s last return type is an `Array<Felt252>` wrapped in a
+/// `PanicResult`, which is the only layout the output serialization is defined for. A
+/// bare `Array<Felt252>` is rejected.
+fn check_only_array_felt_return_type(func: &Function) -> Result<(), Error> {
+    let return_type_id = func
+        .signature
+        .ret_types
+        .last()
+        .ok_or(Error::NoRetTypesInSignature)?;
+    let name = return_type_id
+        .debug_name
+        .as_ref()
+        .ok_or_else(|| Error::TypeIdNoDebugName(return_type_id.clone()))?;
+    // The serialization tail in `create_entry_code` hard-assumes a `PanicResult`
+    // three-cell layout `(tag, array_start, array_end)` wrapping a single
+    // `Array<Felt252>`. Require exactly that shape so a bare array (two cells) or a
+    // multi-value tuple cannot compile-pass here and then corrupt the output segment.
+    if name == "core::panics::PanicResult::<(core::array::Array::<core::felt252>,)>" {
+        Ok(())
+    } else {
+        Err(Error::IllegalReturnValue)
+    }
+}
+
+/// Checks that every non-builtin parameter of `main` is an `Array<Felt252>`.
+fn check_only_array_felt_input_type(
+    sierra_program_registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    func: &Function,
+) -> Result<(), Error> {
+    for ty in func.signature.param_types.iter() {
+        let info =
+            get_info(sierra_program_registry, ty).ok_or_else(|| Error::NoInfoForType(ty.clone()))?;
+        let generic_ty = &info.long_id.generic_id;
+        // Builtins and the implicit gas/system/segment-arena params are supplied by the
+        // runner, not by the user, so they are allowed.
+        if generic_ty == &PoseidonType::ID
+            || generic_ty == &EcOpType::ID
+            || generic_ty == &BitwiseType::ID
+            || generic_ty == &RangeCheckType::ID
+            || generic_ty == &PedersenType::ID
+            || generic_ty == &GasBuiltinType::ID
+            || generic_ty == &SystemType::ID
+            || generic_ty == &SegmentArenaType::ID
+        {
+            continue;
+        }
+        let name = ty
+            .debug_name
+            .as_ref()
+            .ok_or_else(|| Error::TypeIdNoDebugName(ty.clone()))?;
+        if name.as_str() != "core::array::Array::<core::felt252>" {
+            return Err(Error::IllegalInputValue);
+        }
+    }
+    Ok(())
+}
+
+/// Decodes a slice of felts as a UTF-8 short-string where possible.
+///
+/// Cairo encodes short strings as byte-packed felts, so panic data such as failed
+/// `assert` messages arrives as field elements. Felts whose bytes form printable
+/// ASCII are rendered as text; the rest fall back to their decimal representation.
+fn decode_felts_as_short_string(felts: &[Felt252]) -> String {
+    let mut parts = Vec::new();
+    for felt in felts {
+        let bytes = felt.to_bytes_be();
+        let trimmed: Vec<u8> = bytes.iter().copied().skip_while(|b| *b == 0).collect();
+        let decoded = if !trimmed.is_empty()
+            && trimmed
+                .iter()
+                .all(|b| *b == b' ' || !b.is_ascii_control() && b.is_ascii())
+        {
+            std::str::from_utf8(&trimmed).ok().map(|s| s.to_string())
+        } else {
+            None
+        };
+        parts.push(decoded.unwrap_or_else(|| felt.to_string()));
+    }
+    parts.join(" ")
+}
+
+/// Formats a decoded traceback into one `pc: statement` line per frame.
+fn format_traceback(traceback: &[(usize, String)]) -> String {
+    traceback
+        .iter()
+        .map(|(pc, statement)| format!("    pc=0x{:x}: {}", pc, statement))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Maps a pc offset (relative to the start of the program segment) back to the
+/// Sierra statement it was compiled from, if any.
+fn statement_for_pc(
+    casm_program: &CairoProgram,
+    program_base_offset: usize,
+    pc_offset: usize,
+) -> Option<String> {
+    // The proof mode header and the entry code precede the compiled Sierra program,
+    // so anything below the base belongs to the runner-generated preamble.
+    let Some(relative_offset) = pc_offset.checked_sub(program_base_offset) else {
+        return Some("entry code".to_string());
+    };
+    let statements = &casm_program.debug_info.sierra_statement_info;
+    // Find the last statement whose code offset does not exceed the pc.
+    statements
+        .iter()
+        .enumerate()
+        .take_while(|(_, info)| info.code_offset <= relative_offset)
+        .last()
+        .map(|(idx, info)| format!("Sierra statement #{} (casm offset {})", idx, info.code_offset))
+}
+
+/// Walks the frame pointers starting from the VM's current `(pc, fp)` to build a
+/// traceback, mapping each frame's pc back to its originating Sierra statement.
+fn build_traceback(
+    vm: &VirtualMachine,
+    casm_program: &CairoProgram,
+    program_base_offset: usize,
+) -> Vec<(usize, String)> {
+    let mut traceback = Vec::new();
+    let mut pc = vm.get_pc();
+    let mut fp = vm.get_fp();
+    loop {
+        let statement = statement_for_pc(casm_program, program_base_offset, pc.offset)
+            .unwrap_or_else(|| "unknown".to_string());
+        traceback.push((pc.offset, statement));
+        // The previous frame's return pc sits at `[fp - 1]` and its fp at `[fp - 2]`.
+        if fp.offset < 2 {
+            break;
+        }
+        let Ok(ret_pc_addr) = fp - 1 else { break };
+        let Ok(prev_fp_addr) = fp - 2 else { break };
+        let (Ok(ret_pc), Ok(prev_fp)) = (
+            vm.get_relocatable(ret_pc_addr),
+            vm.get_relocatable(prev_fp_addr),
+        ) else {
+            break;
+        };
+        if prev_fp == fp {
+            break;
+        }
+        pc = ret_pc;
+        fp = prev_fp;
+    }
+    traceback
+}
+
+/// Wraps a VM error with the traceback decoded from the VM's current state.
+fn build_vm_exception(
+    vm: &VirtualMachine,
+    casm_program: &CairoProgram,
+    program_base_offset: usize,
+    inner: Error,
+) -> Error {
+    let traceback = build_traceback(vm, casm_program, program_base_offset);
+    Error::VmExceptionWithTrace {
+        inner: Box::new(inner),
+        traceback,
+    }
+}
+
 fn additional_initialization(vm: &mut VirtualMachine, data_len: usize) -> Result<(), Error> {
     // Create the builtin cost segment
     let builtin_cost_segment = vm.add_memory_segment();
@@ -596,37 +1034,29 @@ fn create_entry_code(
     casm_program: &CairoProgram,
     type_sizes: &UnorderedHashMap<ConcreteTypeId, i16>,
     func: &Function,
-    initial_gas: usize,
-    proof_mode: bool,
+    append_return_values: bool,
     args: &Vec<FuncArg>,
-) -> Result<(Vec<Instruction>, Vec<BuiltinName>), Error> {
+    config: &Cairo1RunConfig<'_>,
+) -> Result<(Vec<Instruction>, Vec<BuiltinName>, usize), Error> {
+    // When set, the program's return array and inputs are serialized into the output
+    // builtin segment, so an output builtin must be present.
+    let copy_to_output_builtin = config.proof_mode || append_return_values;
     let mut ctx = casm! {};
     // The builtins in the formatting expected by the runner.
-    let (builtins, builtin_offset) = get_function_builtins(func);
-    // Load all vecs to memory.
-    // Load all array args content to memory.
-    let mut array_args_data = vec![];
+    let (builtins, builtin_offset, output_builtin_offset) =
+        get_function_builtins(func, copy_to_output_builtin);
+    // Frame offset of each argument's first reserved cell, recorded as the cells are
+    // reserved so the serialization code below can read the inputs back out of memory.
+    let mut arg_cell_offsets: Vec<usize> = Vec::with_capacity(args.len());
+    // The argument felts and their array segments are written directly into VM
+    // memory by `load_arguments` after initialization. Here we only reserve the
+    // cells they will occupy, so the number of emitted instructions is constant
+    // regardless of how large the input tensors are.
     let mut ap_offset: i16 = 0;
-    for arg in args {
-        let FuncArg::Array(values) = arg else {
-            continue;
-        };
-        array_args_data.push(ap_offset);
-        casm_extend! {ctx,
-            %{ memory[ap + 0] = segments.add() %}
-            ap += 1;
-        }
-        for (i, v) in values.iter().enumerate() {
-            let arr_at = (i + 1) as i16;
-            casm_extend! {ctx,
-                [ap + 0] = (v.to_bigint());
-                [ap + 0] = [[ap - arr_at] + (i as i16)], ap++;
-            };
-        }
-        ap_offset += (1 + values.len()) as i16;
-    }
-    let mut array_args_data_iter = array_args_data.iter();
     let after_arrays_data_offset = ap_offset;
+    // ap offset at which the user arguments start, needed by `load_arguments` to
+    // address the reserved cells relative to the initial ap.
+    let mut args_start_offset: Option<usize> = None;
     let mut arg_iter = args.iter().enumerate();
     let mut param_index = 0;
     let mut expected_arguments_size = 0;
@@ -653,9 +1083,20 @@ fn create_entry_code(
         let info = get_info(sierra_program_registry, ty)
             .ok_or_else(|| Error::NoInfoForType(ty.clone()))?;
         let generic_ty = &info.long_id.generic_id;
-        if let Some(offset) = builtin_offset.get(generic_ty) {
+        if generic_ty == &SystemType::ID {
+            // The `System` builtin backs the Starknet syscall interface. Inject a fresh
+            // segment for the syscall handler alongside the gas/segment-arena setup; the
+            // handler services `storage_read`/`storage_write`, `get_execution_info`,
+            // `emit_event`, `call_contract`, … out of this segment. `System` still has a
+            // slot reserved in the standard builtin order (see `get_function_builtins`).
+            casm_extend! {ctx,
+                %{ memory[ap + 0] = segments.add() %}
+                ap += 1;
+            }
+            ap_offset += 1;
+        } else if let Some(offset) = builtin_offset.get(generic_ty) {
             let mut offset = *offset;
-            if proof_mode {
+            if config.proof_mode {
                 // Everything is off by 2 due to the proof mode header
                 offset += 2;
             }
@@ -663,15 +1104,9 @@ fn create_entry_code(
                 [ap + 0] = [fp - offset], ap++;
             }
             ap_offset += 1;
-        } else if generic_ty == &SystemType::ID {
-            casm_extend! {ctx,
-                %{ memory[ap + 0] = segments.add() %}
-                ap += 1;
-            }
-            ap_offset += 1;
         } else if generic_ty == &GasBuiltinType::ID {
             casm_extend! {ctx,
-                [ap + 0] = initial_gas, ap++;
+                [ap + 0] = (config.initial_gas), ap++;
             }
             ap_offset += 1;
         } else if generic_ty == &SegmentArenaType::ID {
@@ -682,24 +1117,31 @@ fn create_entry_code(
             ap_offset += 1;
         } else {
             let ty_size = type_sizes[ty];
+            if args_start_offset.is_none() {
+                args_start_offset = Some(ap_offset as usize);
+            }
             let param_ap_offset_end = ap_offset + ty_size;
             expected_arguments_size += ty_size;
             while ap_offset < param_ap_offset_end {
                 let Some((arg_index, arg)) = arg_iter.next() else {
                     break;
                 };
+                arg_cell_offsets.push(ap_offset as usize);
+                // The argument data itself is populated later by `load_arguments`;
+                // here we only reserve the cells it will occupy so that `fp` is
+                // positioned correctly when we jump into `main`. A `Single` occupies a
+                // single felt; an `Array` is represented by its `(start, end)` pointer
+                // pair.
                 match arg {
-                    FuncArg::Single(value) => {
+                    FuncArg::Single(_) => {
                         casm_extend! {ctx,
-                            [ap + 0] = (value.to_bigint()), ap++;
+                            ap += 1;
                         }
                         ap_offset += 1;
                     }
-                    FuncArg::Array(values) => {
-                        let offset = -ap_offset + array_args_data_iter.next().unwrap();
+                    FuncArg::Array(_) => {
                         casm_extend! {ctx,
-                            [ap + 0] = [ap + (offset)], ap++;
-                            [ap + 0] = [ap - 1] + (values.len()), ap++;
+                            ap += 2;
                         }
                         ap_offset += 2;
                         if ap_offset > param_ap_offset_end {
@@ -709,6 +1151,23 @@ fn create_entry_code(
                             });
                         }
                     }
+                    // A wide integer occupies one cell per limb (two for `u256`, four
+                    // for `u512`). Reserve them inline; `load_arguments` writes the limb
+                    // values in little-endian order.
+                    FuncArg::Wide(limbs) => {
+                        for _ in limbs {
+                            casm_extend! {ctx,
+                                ap += 1;
+                            }
+                            ap_offset += 1;
+                        }
+                        if ap_offset > param_ap_offset_end {
+                            return Err(Error::ArgumentUnaligned {
+                                param_index,
+                                arg_index,
+                            });
+                        }
+                    }
                 }
             }
             param_index += 1;
@@ -719,6 +1178,7 @@ fn create_entry_code(
         .map(|arg| match arg {
             FuncArg::Single(_) => 1,
             FuncArg::Array(_) => 2,
+            FuncArg::Wide(limbs) => limbs.len() as i16,
         })
         .sum::<i16>();
     if expected_arguments_size != actual_args_size {
@@ -728,18 +1188,188 @@ fn create_entry_code(
         });
     }
 
-    let before_final_call = ctx.current_code_offset;
-    let final_call_size = 3;
-    let offset = final_call_size
+    // Build the instructions that run *after* `main` returns. When serializing the
+    // result we emit in-program CASM that copies the return array and the inputs into
+    // the output builtin segment, so those cells are produced by executed instructions
+    // and therefore constrained by the AIR (rather than poked in from the host after the
+    // run, which the trace would not cover). Otherwise the tail is just `ret`.
+    let mut tail = casm! {};
+    if let Some(output_offset) = output_builtin_offset {
+        // Offset of the output builtin pointer in the frame, matching how the param
+        // builtins are addressed (shifted by the proof-mode header).
+        let o = if config.proof_mode {
+            output_offset + 2
+        } else {
+            output_offset
+        };
+        // Flattened inputs, described as (frame offset, kind, length) so the unrolled
+        // copy below can read each felt straight out of the reserved argument cells.
+        let input_specs: Vec<(i32, &FuncArg)> = arg_cell_offsets
+            .iter()
+            .zip(args.iter())
+            .map(|(off, arg)| (*off as i32, arg))
+            .collect();
+        let input_len: usize = args
+            .iter()
+            .map(|arg| match arg {
+                FuncArg::Single(_) => 1,
+                FuncArg::Array(values) => values.len(),
+                FuncArg::Wide(limbs) => limbs.len(),
+            })
+            .sum();
+        let il = input_len as i32;
+
+        // --- setup: load the output pointer, write the array length, point `dst` at the
+        // first element cell, and lay out the loop invariant [tag, start, end, dst, src].
+        // On entry the `PanicResult` return value sits at [ap-3..ap] = (tag, start, end).
+        casm_extend! {tail,
+            [ap + 0] = [fp - o], ap++;
+            [ap - 2] = [ap + 0] + [ap - 3], ap++;
+            [ap - 1] = [[ap - 2]];
+            [ap + 0] = [ap - 2] + 1, ap++;
+            [ap + 0] = [ap - 6], ap++;
+            [ap + 0] = [ap - 6], ap++;
+            [ap + 0] = [ap - 6], ap++;
+            [ap + 0] = [ap - 4], ap++;
+            [ap + 0] = [ap - 3], ap++;
+        }
+
+        // --- copy loop: while `src != end`, write [src] to [dst] and advance both. The
+        // relative jump targets are the fixed instruction sizes of this block.
+        casm_extend! {tail,
+            [ap - 3] = [ap + 0] + [ap - 1], ap++;
+            jmp rel 4 if [ap - 1] != 0;
+            jmp rel 15;
+            [ap + 0] = [[ap - 2]], ap++;
+            [ap - 1] = [[ap - 4]];
+            [ap + 0] = [ap - 4] + 1, ap++;
+            [ap + 0] = [ap - 4] + 1, ap++;
+            [ap + 0] = [ap - 9], ap++;
+            [ap + 0] = [ap - 9], ap++;
+            [ap + 0] = [ap - 9], ap++;
+            [ap + 0] = [ap - 5], ap++;
+            [ap + 0] = [ap - 5], ap++;
+            jmp rel -16;
+        }
+
+        // --- inputs: write the flattened input length, then each input felt, keeping the
+        // running output pointer `dst` at [ap-1].
+        casm_extend! {tail,
+            [ap + 0] = [ap - 3], ap++;
+            [ap + 0] = (il), ap++;
+            [ap - 1] = [[ap - 2]];
+            [ap + 0] = [ap - 2] + 1, ap++;
+        }
+        for (off, arg) in input_specs {
+            match arg {
+                FuncArg::Single(_) => {
+                    casm_extend! {tail,
+                        [ap + 0] = [fp + off], ap++;
+                        [ap - 1] = [[ap - 2]];
+                        [ap + 0] = [ap - 2] + 1, ap++;
+                    }
+                }
+                FuncArg::Wide(limbs) => {
+                    for limb in 0..limbs.len() as i32 {
+                        let cell = off + limb;
+                        casm_extend! {tail,
+                            [ap + 0] = [fp + cell], ap++;
+                            [ap - 1] = [[ap - 2]];
+                            [ap + 0] = [ap - 2] + 1, ap++;
+                        }
+                    }
+                }
+                FuncArg::Array(values) => {
+                    for idx in 0..values.len() as i32 {
+                        casm_extend! {tail,
+                            [ap + 0] = [[fp + off] + idx], ap++;
+                            [ap - 1] = [[ap - 2]];
+                            [ap + 0] = [ap - 2] + 1, ap++;
+                        }
+                    }
+                }
+            }
+        }
+
+        // --- restore the `PanicResult` at the top of the stack so the caller can still
+        // read it back with `get_return_values`. The copy loop carries (tag, start, end)
+        // forward; after the input copy, the last loop copy of them sits
+        // `9 + 2 * input_len` cells below `ap`, and each push keeps that distance.
+        let back = 9 + 2 * il;
+        casm_extend! {tail,
+            [ap + 0] = [ap - back], ap++;
+            [ap + 0] = [ap - back], ap++;
+            [ap + 0] = [ap - back], ap++;
+        }
+    }
+    casm_extend! {tail,
+        ret;
+    }
+
+    // `call rel offset` must jump from here to `main`'s entry point, which sits right
+    // after the whole entry code (the `call` plus the serialization tail).
+    let call_size = 2;
+    let tail_size: usize = tail.instructions.iter().map(|inst| inst.body.op_size()).sum();
+    let offset = call_size
+        + tail_size
         + casm_program.debug_info.sierra_statement_info[func.entry_point.0].code_offset;
 
     casm_extend! {ctx,
         call rel offset;
-        ret;
     }
-    assert_eq!(before_final_call + final_call_size, ctx.current_code_offset);
+    ctx.instructions.extend(tail.instructions);
 
-    Ok((ctx.instructions, builtins))
+    Ok((ctx.instructions, builtins, args_start_offset.unwrap_or(ap_offset as usize)))
+}
+
+/// Writes the program's arguments directly into the VM memory after initialization.
+///
+/// Single felt arguments are written to the cells the entry code reserved for them
+/// (addressed relative to the initial `ap`), while each array argument is
+/// materialized in its own memory segment and represented on the argument stack by
+/// its `(start, end)` pointer pair. This keeps the entry code's instruction count
+/// constant regardless of how large the input tensors are.
+fn load_arguments(
+    vm: &mut VirtualMachine,
+    args: &[FuncArg],
+    args_start_offset: usize,
+) -> Result<(), Error> {
+    if args.is_empty() {
+        return Ok(());
+    }
+    // `ap` currently sits at the start of the execution segment; the reserved
+    // argument cells begin `args_start_offset` cells further in.
+    let mut arg_ptr = (vm.get_ap() + args_start_offset).map_err(VirtualMachineError::Math)?;
+    for arg in args {
+        match arg {
+            FuncArg::Single(value) => {
+                vm.insert_value(arg_ptr, *value)?;
+                arg_ptr = (arg_ptr + 1).map_err(VirtualMachineError::Math)?;
+            }
+            FuncArg::Array(values) => {
+                let array_start = vm.add_memory_segment();
+                let mut array_end = array_start;
+                for value in values {
+                    vm.insert_value(array_end, *value)?;
+                    array_end = (array_end + 1).map_err(VirtualMachineError::Math)?;
+                }
+                // Store the array's segment pointers into the reserved cells.
+                vm.insert_value(arg_ptr, array_start)?;
+                arg_ptr = (arg_ptr + 1).map_err(VirtualMachineError::Math)?;
+                vm.insert_value(arg_ptr, array_end)?;
+                arg_ptr = (arg_ptr + 1).map_err(VirtualMachineError::Math)?;
+            }
+            FuncArg::Wide(limbs) => {
+                // Write the limbs inline into the cells the entry code reserved, in the
+                // little-endian order (low limb first) that Cairo expects.
+                for limb in limbs {
+                    vm.insert_value(arg_ptr, *limb)?;
+                    arg_ptr = (arg_ptr + 1).map_err(VirtualMachineError::Math)?;
+                }
+            }
+        }
+    }
+    Ok(())
 }
 
 fn get_info<'a>(
@@ -776,15 +1406,28 @@ fn create_metadata(
 
 fn get_function_builtins(
     func: &Function,
+    copy_to_output_builtin: bool,
 ) -> (
     Vec<BuiltinName>,
     HashMap<cairo_lang_sierra::ids::GenericTypeId, i16>,
+    Option<i16>,
 ) {
     let entry_params = &func.signature.param_types;
     let mut builtins = Vec::new();
     let mut builtin_offset: HashMap<cairo_lang_sierra::ids::GenericTypeId, i16> = HashMap::new();
     let mut current_offset = 3;
     // Fetch builtins from the entry_params in the standard order
+    // The `System` builtin (present on Starknet contract entrypoints) is reserved a
+    // slot in the standard order so the return-value stack accounting stays aligned; it
+    // is not a VM builtin, so its segment is injected in the preamble rather than pushed
+    // onto the builtins list.
+    if entry_params
+        .iter()
+        .any(|ti| ti.debug_name == Some("System".into()))
+    {
+        builtin_offset.insert(SystemType::ID, current_offset);
+        current_offset += 1;
+    }
     if entry_params
         .iter()
         .any(|ti| ti.debug_name == Some("Poseidon".into()))
@@ -823,7 +1466,20 @@ fn get_function_builtins(
     {
         builtins.push(BuiltinName::pedersen);
         builtin_offset.insert(PedersenType::ID, current_offset);
+        current_offset += 1;
     }
+    // The output builtin is not a Sierra parameter of `main`; it is appended by the
+    // runner so that the program's result can be serialized into its segment. Pushing
+    // it last places it first once the order is reversed into the standard layout. Its
+    // frame offset is returned so the entry code can load the pointer and write the
+    // serialized result through it.
+    let output_builtin_offset = if copy_to_output_builtin {
+        let offset = current_offset;
+        builtins.push(BuiltinName::output);
+        Some(offset)
+    } else {
+        None
+    };
     builtins.reverse();
-    (builtins, builtin_offset)
+    (builtins, builtin_offset, output_builtin_offset)
 }
\ No newline at end of file
@@ -0,0 +1,458 @@
+//! A Sierra-level interpreter used for differential validation of the CASM runner.
+//!
+//! Instead of lowering a Sierra [`Function`] to CASM and executing it on the Cairo VM
+//! (see [`crate::runner`]), this backend walks the program statement-by-statement,
+//! executing each libfunc directly over an in-memory value registry. Builtin pointers
+//! (Poseidon, Pedersen, Bitwise, EcOp, RangeCheck) are carried as opaque tokens rather
+//! than as memory segments; the libfuncs that use them (hashes, bitwise and EC
+//! operations, range checks) are not interpreted and are reported as unsupported.
+//! There is no trace or proof — only the return values and the final gas counter.
+//!
+//! Running the same Sierra program through both backends and comparing the results is
+//! a cheap way to flag trace/output divergences and localize which libfunc introduced
+//! them, which is valuable when benchmarking many small ML layers.
+
+use super::{Cairo1RunConfig, Error, FuncArg, FuncArgs, ReturnValue};
+use cairo_lang_sierra::extensions::core::{CoreConcreteLibfunc, CoreLibfunc, CoreType};
+use cairo_lang_sierra::extensions::felt252::{
+    Felt252BinaryOperationConcrete, Felt252BinaryOperator, Felt252Concrete,
+};
+use cairo_lang_sierra::ids::VarId;
+use cairo_lang_sierra::program::{GenStatement, Program as SierraProgram, StatementIdx};
+use cairo_lang_sierra::program_registry::ProgramRegistry;
+use cairo_lang_sierra_gas::gas_info::GasInfo;
+use cairo_vm::Felt252;
+use std::collections::HashMap;
+
+/// A runtime value produced while interpreting a Sierra program.
+///
+/// Builtins are threaded through as opaque [`Value::Builtin`] tokens so the statement
+/// walker can move them between variables without modeling their segments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Felt(Felt252),
+    Array(Vec<Value>),
+    Struct(Vec<Value>),
+    Enum { index: usize, payload: Box<Value> },
+    /// A builtin pointer or the unit type `()`, carried without semantics.
+    Builtin,
+}
+
+impl Value {
+    fn as_felt(&self) -> Result<Felt252, Error> {
+        match self {
+            Value::Felt(felt) => Ok(*felt),
+            _ => Err(Error::InterpreterTypeMismatch),
+        }
+    }
+}
+
+/// The outcome of interpreting a function: its return values and the gas left over.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpreterResult {
+    pub return_values: Vec<Value>,
+    pub remaining_gas: i64,
+}
+
+/// Interprets `func` within `sierra_program`, seeding its parameters from `args` the
+/// same way the CASM preamble does, and returns the function's return values together
+/// with the final gas counter.
+pub fn interpret(
+    sierra_program: &SierraProgram,
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    gas_info: &GasInfo,
+    func: &cairo_lang_sierra::program::Function,
+    args: &[FuncArg],
+    initial_gas: i64,
+) -> Result<InterpreterResult, Error> {
+    let mut interpreter = Interpreter {
+        program: sierra_program,
+        registry,
+        gas_info,
+        registers: HashMap::new(),
+        gas: initial_gas,
+    };
+    interpreter.seed_arguments(func, args)?;
+    interpreter.run(func)
+}
+
+struct Interpreter<'a> {
+    program: &'a SierraProgram,
+    registry: &'a ProgramRegistry<CoreType, CoreLibfunc>,
+    gas_info: &'a GasInfo,
+    registers: HashMap<VarId, Value>,
+    gas: i64,
+}
+
+impl Interpreter<'_> {
+    /// Binds the function's parameters to the supplied [`FuncArg`]s. Builtin params are
+    /// bound to opaque tokens in declaration order, mirroring how `create_entry_code`
+    /// injects them ahead of the user arguments.
+    fn seed_arguments(
+        &mut self,
+        func: &cairo_lang_sierra::program::Function,
+        args: &[FuncArg],
+    ) -> Result<(), Error> {
+        let mut args = args.iter();
+        for param in func.signature.param_types.iter().zip(func.params.iter()) {
+            let (ty, param) = param;
+            // Builtins and the implicit gas/system params are not user-supplied; bind
+            // them to opaque tokens so the libfuncs that thread them keep type-checking.
+            let is_builtin = ty
+                .debug_name
+                .as_ref()
+                .map(|n| is_builtin_type(n))
+                .unwrap_or(false);
+            if is_builtin {
+                self.registers.insert(param.id.clone(), Value::Builtin);
+                continue;
+            }
+            let value = match args.next() {
+                Some(FuncArg::Single(felt)) => Value::Felt(*felt),
+                Some(FuncArg::Array(felts)) => {
+                    Value::Array(felts.iter().copied().map(Value::Felt).collect())
+                }
+                // A wide integer is seeded as the struct of its 128-bit limbs, matching
+                // how `u256`/`u512` are represented in Sierra.
+                Some(FuncArg::Wide(limbs)) => {
+                    Value::Struct(limbs.iter().copied().map(Value::Felt).collect())
+                }
+                None => return Err(Error::InterpreterMissingArgument),
+            };
+            self.registers.insert(param.id.clone(), value);
+        }
+        Ok(())
+    }
+
+    fn run(
+        &mut self,
+        func: &cairo_lang_sierra::program::Function,
+    ) -> Result<InterpreterResult, Error> {
+        let mut pc = func.entry_point;
+        loop {
+            self.charge_gas(pc);
+            let statement = self
+                .program
+                .statements
+                .get(pc.0)
+                .ok_or(Error::InterpreterInvalidPc)?;
+            match statement {
+                GenStatement::Return(ret) => {
+                    let return_values = ret
+                        .iter()
+                        .map(|var| self.take(var))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    return Ok(InterpreterResult {
+                        return_values,
+                        remaining_gas: self.gas,
+                    });
+                }
+                GenStatement::Invocation(invocation) => {
+                    let libfunc = self.registry.get_libfunc(&invocation.libfunc_id)?;
+                    let inputs = invocation
+                        .args
+                        .iter()
+                        .map(|var| self.take(var))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let (branch, outputs) = self.eval_libfunc(libfunc, inputs)?;
+                    let branch_info = &invocation.branches[branch];
+                    for (var, value) in branch_info.results.iter().zip(outputs) {
+                        self.registers.insert(var.clone(), value);
+                    }
+                    pc = match branch_info.target {
+                        cairo_lang_sierra::program::GenBranchTarget::Fallthrough => {
+                            StatementIdx(pc.0 + 1)
+                        }
+                        cairo_lang_sierra::program::GenBranchTarget::Statement(idx) => idx,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Executes a single libfunc, returning the index of the branch taken and the
+    /// values produced on that branch.
+    fn eval_libfunc(
+        &mut self,
+        libfunc: &CoreConcreteLibfunc,
+        mut inputs: Vec<Value>,
+    ) -> Result<(usize, Vec<Value>), Error> {
+        match libfunc {
+            // felt252 arithmetic, modeled directly over the field.
+            CoreConcreteLibfunc::Felt252(Felt252Concrete::BinaryOperation(op)) => {
+                let (lhs, rhs, operator) = match op {
+                    Felt252BinaryOperationConcrete::WithVar(op) => {
+                        (inputs.remove(0).as_felt()?, inputs.remove(0).as_felt()?, op.operator)
+                    }
+                    Felt252BinaryOperationConcrete::WithConst(op) => {
+                        (inputs.remove(0).as_felt()?, op.c.clone().into(), op.operator)
+                    }
+                };
+                let result = match operator {
+                    Felt252BinaryOperator::Add => lhs + rhs,
+                    Felt252BinaryOperator::Sub => lhs - rhs,
+                    Felt252BinaryOperator::Mul => lhs * rhs,
+                    Felt252BinaryOperator::Div => {
+                        // A zero divisor is a runtime condition, not a bug in the
+                        // interpreter, so surface it as an error rather than panicking.
+                        let divisor = rhs
+                            .try_into()
+                            .map_err(|_| Error::InterpreterDivisionByZero)?;
+                        lhs.field_div(&divisor)
+                    }
+                };
+                Ok((0, vec![Value::Felt(result)]))
+            }
+            CoreConcreteLibfunc::Felt252(Felt252Concrete::Const(c)) => {
+                Ok((0, vec![Value::Felt(c.c.clone().into())]))
+            }
+            CoreConcreteLibfunc::Felt252(Felt252Concrete::IsZero(_)) => {
+                let value = inputs.remove(0).as_felt()?;
+                if value == Felt252::ZERO {
+                    Ok((0, vec![]))
+                } else {
+                    Ok((1, vec![Value::Felt(value)]))
+                }
+            }
+
+            // Array primitives, backed by a plain `Vec<Value>`.
+            CoreConcreteLibfunc::Array(array) => self.eval_array(array, inputs),
+
+            // Struct construct/deconstruct is just (un)grouping the inputs.
+            CoreConcreteLibfunc::Struct(s) => {
+                use cairo_lang_sierra::extensions::structure::StructConcreteLibfunc;
+                match s {
+                    StructConcreteLibfunc::Construct(_) => {
+                        Ok((0, vec![Value::Struct(inputs)]))
+                    }
+                    StructConcreteLibfunc::Deconstruct(_)
+                    | StructConcreteLibfunc::SnapshotDeconstruct(_) => match inputs.remove(0) {
+                        Value::Struct(members) => Ok((0, members)),
+                        _ => Err(Error::InterpreterTypeMismatch),
+                    },
+                }
+            }
+
+            // Enum construct tags the payload; match dispatches on the tag.
+            CoreConcreteLibfunc::Enum(e) => {
+                use cairo_lang_sierra::extensions::enm::EnumConcreteLibfunc;
+                match e {
+                    EnumConcreteLibfunc::Init(init) => Ok((
+                        0,
+                        vec![Value::Enum {
+                            index: init.index,
+                            payload: Box::new(inputs.remove(0)),
+                        }],
+                    )),
+                    EnumConcreteLibfunc::Match(_) | EnumConcreteLibfunc::SnapshotMatch(_) => {
+                        match inputs.remove(0) {
+                            Value::Enum { index, payload } => Ok((index, vec![*payload])),
+                            _ => Err(Error::InterpreterTypeMismatch),
+                        }
+                    }
+                    EnumConcreteLibfunc::FromBoundedInt(_) => Ok((0, inputs)),
+                }
+            }
+
+            // Gas withdrawal drives the branch selection off the live gas counter.
+            CoreConcreteLibfunc::Gas(gas) => {
+                use cairo_lang_sierra::extensions::gas::GasConcreteLibfunc;
+                match gas {
+                    GasConcreteLibfunc::WithdrawGas(_)
+                    | GasConcreteLibfunc::BuiltinWithdrawGas(_) => {
+                        // The per-statement charge already accounts for the cost; here we
+                        // only model success/failure on the remaining balance.
+                        if self.gas >= 0 {
+                            Ok((0, vec![Value::Builtin]))
+                        } else {
+                            Ok((1, vec![Value::Builtin]))
+                        }
+                    }
+                    GasConcreteLibfunc::RedepositGas(_) => Ok((0, vec![Value::Builtin])),
+                    GasConcreteLibfunc::GetAvailableGas(_) => {
+                        Ok((0, vec![Value::Builtin, Value::Felt(self.gas.into())]))
+                    }
+                }
+            }
+
+            // Memory/no-op libfuncs that simply forward their operands.
+            CoreConcreteLibfunc::Drop(_) => Ok((0, vec![])),
+            CoreConcreteLibfunc::Dup(_) => {
+                let value = inputs.remove(0);
+                Ok((0, vec![value.clone(), value]))
+            }
+            CoreConcreteLibfunc::SnapshotTake(_) => {
+                let value = inputs.remove(0);
+                Ok((0, vec![value.clone(), value]))
+            }
+            CoreConcreteLibfunc::Mem(_) | CoreConcreteLibfunc::Branch(_) => Ok((0, inputs)),
+
+            // Any libfunc we do not model yet is reported so the mismatch is localized
+            // rather than silently producing a wrong result.
+            _ => Err(Error::InterpreterUnsupportedLibfunc),
+        }
+    }
+
+    fn eval_array(
+        &mut self,
+        array: &cairo_lang_sierra::extensions::array::ArrayConcreteLibfunc,
+        mut inputs: Vec<Value>,
+    ) -> Result<(usize, Vec<Value>), Error> {
+        use cairo_lang_sierra::extensions::array::ArrayConcreteLibfunc;
+        match array {
+            ArrayConcreteLibfunc::New(_) => Ok((0, vec![Value::Array(Vec::new())])),
+            ArrayConcreteLibfunc::Append(_) => {
+                let mut arr = match inputs.remove(0) {
+                    Value::Array(arr) => arr,
+                    _ => return Err(Error::InterpreterTypeMismatch),
+                };
+                arr.push(inputs.remove(0));
+                Ok((0, vec![Value::Array(arr)]))
+            }
+            ArrayConcreteLibfunc::Len(_) => {
+                let len = match &inputs[0] {
+                    Value::Array(arr) => arr.len(),
+                    _ => return Err(Error::InterpreterTypeMismatch),
+                };
+                Ok((0, vec![Value::Felt(len.into())]))
+            }
+            ArrayConcreteLibfunc::Get(_) | ArrayConcreteLibfunc::SnapshotPopFront(_) => {
+                let arr = match &mut inputs[0] {
+                    Value::Array(arr) => arr,
+                    _ => return Err(Error::InterpreterTypeMismatch),
+                };
+                if arr.is_empty() {
+                    Ok((1, vec![inputs.remove(0)]))
+                } else {
+                    let element = arr.remove(0);
+                    let rest = inputs.remove(0);
+                    Ok((0, vec![rest, element]))
+                }
+            }
+            // Remaining array libfuncs are not exercised by the benchmarked models.
+            _ => Err(Error::InterpreterUnsupportedLibfunc),
+        }
+    }
+
+    /// Decrements the gas counter by the cost charged to `statement`, summed over every
+    /// cost token kind, using the same [`GasInfo`] the CASM compiler consumed.
+    fn charge_gas(&mut self, statement: StatementIdx) {
+        let cost: i64 = self
+            .gas_info
+            .variable_values
+            .iter()
+            .filter(|((idx, _), _)| *idx == statement)
+            .map(|(_, value)| *value)
+            .sum();
+        self.gas -= cost;
+    }
+
+    fn take(&mut self, var: &VarId) -> Result<Value, Error> {
+        self.registers
+            .get(var)
+            .cloned()
+            .ok_or(Error::InterpreterUnboundVariable)
+    }
+}
+
+/// Differential-validation harness: runs `main` through both the CASM VM
+/// ([`crate::runner::run`]) and this interpreter over the same arguments, and checks
+/// that they agree on the flattened felt return values. A divergence is surfaced as
+/// [`Error::DifferentialMismatch`] so the offending libfunc can be localized.
+///
+/// The CASM side runs in execution mode (`proof_mode: false`) so that `main` may have an
+/// arbitrary signature and its raw return values are produced without output
+/// serialization, matching what the interpreter yields.
+pub async fn differential_validate(
+    sierra_program: &SierraProgram,
+    args: &FuncArgs,
+    initial_gas: usize,
+) -> Result<InterpreterResult, Error> {
+    let config = Cairo1RunConfig {
+        proof_mode: false,
+        trace_enabled: false,
+        air_public_input: None,
+        initial_gas,
+        ..Cairo1RunConfig::default()
+    };
+    let casm_result = super::run(
+        sierra_program,
+        &None,
+        &None,
+        &None,
+        &None,
+        args,
+        false,
+        &config,
+        None,
+    )
+    .await?;
+
+    let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(sierra_program)?;
+    let metadata = super::create_metadata(sierra_program, Some(Default::default()))?;
+    let func = super::find_function(sierra_program, "::main")?;
+    let interp_result = interpret(
+        sierra_program,
+        &registry,
+        &metadata.gas_info,
+        func,
+        &args.0,
+        initial_gas as i64,
+    )?;
+
+    let casm_felts = flatten_return_values(&casm_result.0);
+    let interp_felts = flatten_values(&interp_result.return_values);
+    if casm_felts != interp_felts {
+        return Err(Error::DifferentialMismatch {
+            casm: casm_felts,
+            interpreter: interp_felts,
+        });
+    }
+    Ok(interp_result)
+}
+
+/// Collapses the CASM runner's nested [`ReturnValue`]s into the felt sequence they
+/// ultimately encode, so the two backends can be compared on equal footing.
+fn flatten_return_values(values: &[ReturnValue]) -> Vec<Felt252> {
+    let mut out = Vec::new();
+    for value in values {
+        match value {
+            ReturnValue::Int(felt) => out.push(*felt),
+            ReturnValue::Array(arr) => out.extend(flatten_return_values(arr)),
+        }
+    }
+    out
+}
+
+/// The interpreter-side counterpart of [`flatten_return_values`]: builtins carry no felt
+/// payload and are dropped, matching the CASM runner which never returns them.
+fn flatten_values(values: &[Value]) -> Vec<Felt252> {
+    let mut out = Vec::new();
+    for value in values {
+        match value {
+            Value::Felt(felt) => out.push(*felt),
+            Value::Array(members) | Value::Struct(members) => {
+                out.extend(flatten_values(members))
+            }
+            Value::Enum { payload, .. } => {
+                out.extend(flatten_values(std::slice::from_ref(payload)))
+            }
+            Value::Builtin => {}
+        }
+    }
+    out
+}
+
+fn is_builtin_type(name: &str) -> bool {
+    matches!(
+        name,
+        "RangeCheck"
+            | "Poseidon"
+            | "EcOp"
+            | "Bitwise"
+            | "Pedersen"
+            | "GasBuiltin"
+            | "System"
+            | "SegmentArena"
+    )
+}
@@ -0,0 +1,339 @@
+//! A pluggable syscall handler for running Starknet contract entrypoints.
+//!
+//! Plain Cairo functions only need the gas/segment-arena setup that the preamble in
+//! [`crate::runner`] already injects. A `#[starknet::contract]` entrypoint additionally
+//! takes the `System` builtin and issues syscalls (`storage_read`/`storage_write`,
+//! `get_execution_info`, `emit_event`, `call_contract`, …) against it. [`SyscallHandler`]
+//! abstracts those syscalls so the benchmark can execute real contract entrypoints
+//! against deterministic state; [`DefaultSyscallHandler`] provides an in-memory backing
+//! suitable for reproducible measurements.
+
+use cairo_lang_casm::hints::{Hint, StarknetHint};
+use cairo_lang_casm::operand::ResOperand;
+use cairo_vm::hint_processor::cairo_1_hint_processor::hint_processor::Cairo1HintProcessor;
+use cairo_vm::hint_processor::cairo_1_hint_processor::hint_processor_utils::{
+    extract_buffer, get_ptr,
+};
+use cairo_vm::hint_processor::hint_processor_definition::{
+    HintProcessorLogic, HintReference, ResourceTracker,
+};
+use cairo_vm::serde::deserialize_program::ApTracking;
+use cairo_vm::types::exec_scope::ExecutionScopes;
+use cairo_vm::types::relocatable::{MaybeRelocatable, Relocatable};
+use cairo_vm::vm::errors::hint_errors::HintError;
+use cairo_vm::vm::errors::vm_errors::VirtualMachineError;
+use cairo_vm::vm::runners::cairo_runner::RunResources;
+use cairo_vm::vm::vm_core::VirtualMachine;
+use cairo_vm::Felt252;
+use std::any::Any;
+use std::collections::HashMap;
+
+/// The execution context a contract observes through `get_execution_info`.
+///
+/// Kept minimal and fully owned so runs are reproducible: the same handler configured
+/// the same way always reports the same context.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionInfo {
+    pub block_number: u64,
+    pub block_timestamp: u64,
+    pub caller_address: Felt252,
+    pub contract_address: Felt252,
+    pub entry_point_selector: Felt252,
+}
+
+/// An event emitted by a contract via the `emit_event` syscall.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub keys: Vec<Felt252>,
+    pub data: Vec<Felt252>,
+}
+
+/// Services the syscalls issued by a Starknet contract entrypoint.
+///
+/// Each method mirrors the corresponding Cairo syscall. The trait has no default
+/// methods: an implementor provides every one; [`DefaultSyscallHandler`] is an
+/// in-memory implementation.
+pub trait SyscallHandler {
+    /// Reads the value stored at `key` in `address_domain`, defaulting to zero.
+    fn storage_read(&mut self, address_domain: u32, key: Felt252) -> Felt252;
+
+    /// Writes `value` at `key` in `address_domain`.
+    fn storage_write(&mut self, address_domain: u32, key: Felt252, value: Felt252);
+
+    /// Returns the execution context reported to the contract.
+    fn get_execution_info(&self) -> ExecutionInfo;
+
+    /// Records an emitted event.
+    fn emit_event(&mut self, keys: Vec<Felt252>, data: Vec<Felt252>);
+
+    /// Dispatches a call to another contract, returning its raw return data.
+    fn call_contract(
+        &mut self,
+        address: Felt252,
+        selector: Felt252,
+        calldata: Vec<Felt252>,
+    ) -> Vec<Felt252>;
+}
+
+/// An in-memory [`SyscallHandler`] backing storage with a map and returning a fixed
+/// [`ExecutionInfo`]. Emitted events are collected for inspection after a run.
+#[derive(Debug, Default)]
+pub struct DefaultSyscallHandler {
+    storage: HashMap<(u32, Felt252), Felt252>,
+    execution_info: ExecutionInfo,
+    pub events: Vec<Event>,
+}
+
+impl DefaultSyscallHandler {
+    pub fn new(execution_info: ExecutionInfo) -> Self {
+        Self {
+            storage: HashMap::new(),
+            execution_info,
+            events: Vec::new(),
+        }
+    }
+}
+
+impl SyscallHandler for DefaultSyscallHandler {
+    fn storage_read(&mut self, address_domain: u32, key: Felt252) -> Felt252 {
+        self.storage
+            .get(&(address_domain, key))
+            .copied()
+            .unwrap_or(Felt252::ZERO)
+    }
+
+    fn storage_write(&mut self, address_domain: u32, key: Felt252, value: Felt252) {
+        self.storage.insert((address_domain, key), value);
+    }
+
+    fn get_execution_info(&self) -> ExecutionInfo {
+        self.execution_info.clone()
+    }
+
+    fn emit_event(&mut self, keys: Vec<Felt252>, data: Vec<Felt252>) {
+        self.events.push(Event { keys, data });
+    }
+
+    fn call_contract(
+        &mut self,
+        _address: Felt252,
+        _selector: Felt252,
+        _calldata: Vec<Felt252>,
+    ) -> Vec<Felt252> {
+        // The in-memory handler has no other contracts to dispatch to; real benchmarks
+        // override this to model cross-contract calls.
+        Vec::new()
+    }
+}
+
+/// The short-string felt selector Cairo emits for a syscall, e.g. `'StorageRead'`.
+fn selector_felt(name: &str) -> Felt252 {
+    Felt252::from_bytes_be_slice(name.as_bytes())
+}
+
+/// A sequential read/write head over the syscall segment. Inputs are consumed from the
+/// request cells and the response is written immediately after, keeping the host side of
+/// the Cairo 1 syscall ABI in one place.
+struct Cursor<'v> {
+    vm: &'v mut VirtualMachine,
+    ptr: Relocatable,
+}
+
+impl<'v> Cursor<'v> {
+    fn new(vm: &'v mut VirtualMachine, ptr: Relocatable) -> Self {
+        Self { vm, ptr }
+    }
+
+    fn read_felt(&mut self) -> Result<Felt252, HintError> {
+        let value = self.vm.get_integer(self.ptr)?.into_owned();
+        self.ptr = (self.ptr + 1)?;
+        Ok(value)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, HintError> {
+        let bytes = self.read_felt()?.to_bytes_le();
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_ptr(&mut self) -> Result<Relocatable, HintError> {
+        let value = self.vm.get_relocatable(self.ptr)?;
+        self.ptr = (self.ptr + 1)?;
+        Ok(value)
+    }
+
+    /// Reads a Cairo array, passed as a `(start, end)` pointer pair, into its felts.
+    fn read_array(&mut self) -> Result<Vec<Felt252>, HintError> {
+        let start = self.read_ptr()?;
+        let end = self.read_ptr()?;
+        let len = (end - start)?;
+        Ok(self
+            .vm
+            .get_integer_range(start, len)?
+            .into_iter()
+            .map(|felt| *felt.as_ref())
+            .collect())
+    }
+
+    fn write(&mut self, value: MaybeRelocatable) -> Result<(), HintError> {
+        self.vm.insert_value(self.ptr, value)?;
+        self.ptr = (self.ptr + 1)?;
+        Ok(())
+    }
+
+    /// Allocates a fresh segment holding `data` and returns its base pointer.
+    fn alloc(&mut self, data: &[Felt252]) -> Result<Relocatable, HintError> {
+        let base = self.vm.add_memory_segment();
+        let cells: Vec<MaybeRelocatable> =
+            data.iter().map(|felt| MaybeRelocatable::from(*felt)).collect();
+        self.vm.load_data(base, &cells)?;
+        Ok(base)
+    }
+
+    /// Allocates `data` into a fresh segment and returns the `(start, end)` pointer pair
+    /// a Cairo array is represented by.
+    fn alloc_array(&mut self, data: &[Felt252]) -> Result<(Relocatable, Relocatable), HintError> {
+        let start = self.alloc(data)?;
+        let end = (start + data.len())?;
+        Ok((start, end))
+    }
+
+    /// Writes the response header (remaining gas, a zero failure flag) followed by the syscall's
+    /// results.
+    fn write_response(
+        &mut self,
+        gas: Felt252,
+        results: &[MaybeRelocatable],
+    ) -> Result<(), HintError> {
+        self.write(MaybeRelocatable::from(gas))?;
+        self.write(MaybeRelocatable::from(Felt252::ZERO))?;
+        for result in results {
+            self.write(result.clone())?;
+        }
+        Ok(())
+    }
+}
+
+/// Wraps a [`Cairo1HintProcessor`] so that Starknet system calls are dispatched to a
+/// user-supplied [`SyscallHandler`] instead of the built-in stubs. Every other hint is
+/// forwarded to the inner processor unchanged, so plain Cairo programs run exactly as
+/// before.
+pub struct SyscallHintProcessor<'a> {
+    inner: Cairo1HintProcessor,
+    handler: &'a mut dyn SyscallHandler,
+}
+
+impl<'a> SyscallHintProcessor<'a> {
+    pub fn new(inner: Cairo1HintProcessor, handler: &'a mut dyn SyscallHandler) -> Self {
+        Self { inner, handler }
+    }
+
+    /// Resolves the `System` buffer operand to the syscall pointer it currently holds.
+    fn syscall_ptr(vm: &VirtualMachine, system: &ResOperand) -> Result<Relocatable, HintError> {
+        let (cell, base_offset) = extract_buffer(system)?;
+        get_ptr(vm, cell, &base_offset)
+    }
+
+    /// Services a single Starknet system call against the handler, following the Cairo 1
+    /// syscall ABI: the request segment begins with the selector and the gas counter,
+    /// followed by the syscall-specific arguments; the response is written immediately
+    /// after, beginning with the (unchanged) gas counter and a zero failure flag.
+    fn dispatch(&mut self, vm: &mut VirtualMachine, system: &ResOperand) -> Result<(), HintError> {
+        let ptr = Self::syscall_ptr(vm, system)?;
+        let selector = vm.get_integer(ptr)?.into_owned();
+        let gas = vm.get_integer((ptr + 1)?)?.into_owned();
+        let mut cursor = Cursor::new(vm, (ptr + 2)?);
+
+        if selector == selector_felt("StorageRead") {
+            let address_domain = cursor.read_u32()?;
+            let key = cursor.read_felt()?;
+            let value = self.handler.storage_read(address_domain, key);
+            cursor.write_response(gas, &[value.into()])?;
+        } else if selector == selector_felt("StorageWrite") {
+            let address_domain = cursor.read_u32()?;
+            let key = cursor.read_felt()?;
+            let value = cursor.read_felt()?;
+            self.handler.storage_write(address_domain, key, value);
+            cursor.write_response(gas, &[])?;
+        } else if selector == selector_felt("GetExecutionInfo") {
+            let info = self.handler.get_execution_info();
+            let segment = cursor.alloc(&[
+                info.block_number.into(),
+                info.block_timestamp.into(),
+                info.caller_address,
+                info.contract_address,
+                info.entry_point_selector,
+            ])?;
+            cursor.write_response(gas, &[segment.into()])?;
+        } else if selector == selector_felt("EmitEvent") {
+            let keys = cursor.read_array()?;
+            let data = cursor.read_array()?;
+            self.handler.emit_event(keys, data);
+            cursor.write_response(gas, &[])?;
+        } else if selector == selector_felt("CallContract") {
+            let address = cursor.read_felt()?;
+            let entry_point_selector = cursor.read_felt()?;
+            let calldata = cursor.read_array()?;
+            let retdata = self
+                .handler
+                .call_contract(address, entry_point_selector, calldata);
+            let (start, end) = cursor.alloc_array(&retdata)?;
+            cursor.write_response(gas, &[start.into(), end.into()])?;
+        } else {
+            return Err(HintError::CustomHint(
+                "unsupported syscall selector".to_string().into_boxed_str(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl HintProcessorLogic for SyscallHintProcessor<'_> {
+    fn execute_hint(
+        &mut self,
+        vm: &mut VirtualMachine,
+        exec_scopes: &mut ExecutionScopes,
+        hint_data: &Box<dyn Any>,
+        constants: &HashMap<String, Felt252>,
+    ) -> Result<(), HintError> {
+        // Intercept only the system-call hint; everything else runs on the inner
+        // processor exactly as it would without a syscall handler.
+        if let Some(Hint::Starknet(StarknetHint::SystemCall { system })) =
+            hint_data.downcast_ref::<Hint>()
+        {
+            let system = system.clone();
+            self.dispatch(vm, &system)
+        } else {
+            self.inner
+                .execute_hint(vm, exec_scopes, hint_data, constants)
+        }
+    }
+
+    fn compile_hint(
+        &self,
+        hint_code: &str,
+        ap_tracking_data: &ApTracking,
+        reference_ids: &HashMap<String, usize>,
+        references: &[HintReference],
+    ) -> Result<Box<dyn Any>, VirtualMachineError> {
+        self.inner
+            .compile_hint(hint_code, ap_tracking_data, reference_ids, references)
+    }
+}
+
+impl ResourceTracker for SyscallHintProcessor<'_> {
+    fn consumed(&self) -> bool {
+        self.inner.consumed()
+    }
+
+    fn consume_step(&mut self) {
+        self.inner.consume_step()
+    }
+
+    fn get_n_steps(&self) -> Option<usize> {
+        self.inner.get_n_steps()
+    }
+
+    fn run_resources(&self) -> &RunResources {
+        self.inner.run_resources()
+    }
+}